@@ -2,13 +2,21 @@
 //! zoekt-mcp — MCP server wrapping Zoekt's HTTP JSON API for Claude Code
 //!
 //! Tools:
-//!   search     — trigram-indexed code search (regex, file:, lang:, sym:, repo: filters)
-//!   list_repos — list all indexed repositories with document counts and sizes
+//!   search        — trigram-indexed code search (regex, file:, lang:, sym:, repo: filters,
+//!                   plus ripgrep-style type: aliases, e.g. type:rust, optional tree-sitter
+//!                   scope expansion via expand_scope, and offset/next_cursor pagination)
+//!   get_file      — fetch the whole contents of a single file by repo + path
+//!   list_repos    — list all indexed repositories with document counts and sizes
+//!   types         — list the built-in type: alias table
+//!   cancel_search — abort an in-flight search() started with a matching search_id
+//!   context_pack  — rank, dedup, and char-budget search hits into one assembled context blob
 //!
 //! Environment:
-//!   ZOEKT_URL  — Zoekt webserver base URL (default: http://localhost:6070)
+//!   ZOEKT_URL        — Zoekt webserver base URL (default: http://localhost:6070)
+//!   ZOEKT_TIMEOUT_MS — per-request timeout against Zoekt, in ms (default: 30000)
 
 use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use futures::future::join_all;
 use rmcp::{
     ServerHandler, ServiceExt,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
@@ -17,14 +25,18 @@ use rmcp::{
     transport::stdio,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 // ── Tool input types ────────────────────────────────────────────────────────
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 struct SearchInput {
     #[schemars(
-        description = "Zoekt query. Supports regex and filters: file:pattern lang:rust sym:funcname repo:name case:yes/no branch:name. Examples: 'fn main', 'file:\\.rs$ async fn', 'lang:nix mkDerivation'"
+        description = "Zoekt query. Supports regex and filters: file:pattern lang:rust sym:funcname repo:name case:yes/no branch:name, plus ripgrep-style type:alias (e.g. type:rust, type:cpp — see the `types` tool for the full list). Examples: 'fn main', 'file:\\.rs$ async fn', 'type:rust async fn'"
     )]
     query: String,
 
@@ -38,6 +50,30 @@ struct SearchInput {
         description = "Output mode: \"content\" shows matching lines (supports -A/-B/-C context, -n line numbers, head_limit), \"files_with_matches\" shows file paths (supports head_limit), \"count\" shows match counts (supports head_limit). Defaults to \"files_with_matches\"."
     )]
     output_mode: Option<String>,
+
+    #[schemars(
+        description = "Optional caller-supplied id for this search. Pass the same id to cancel_search to abort it while it's in flight."
+    )]
+    search_id: Option<String>,
+
+    #[schemars(
+        description = "In \"content\" mode, expand each match to its enclosing function/class via tree-sitter instead of a fixed context window. Falls back to context_lines when no grammar is available or parsing fails."
+    )]
+    expand_scope: Option<bool>,
+
+    #[schemars(description = "Cap on lines printed per expanded scope (default 200)")]
+    max_expand_lines: Option<u32>,
+
+    #[schemars(
+        description = "Skip this many files before applying limit. Pass the next_cursor from a previous response to page through a large result set. Defaults to 0."
+    )]
+    offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct CancelSearchInput {
+    #[schemars(description = "The search_id passed to the in-flight search() call to cancel")]
+    search_id: String,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -46,6 +82,128 @@ struct ListReposInput {
     query: Option<String>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct ContextPackInput {
+    #[schemars(
+        description = "Zoekt query, same syntax as search (regex, file:, lang:, sym:, repo:, type: aliases)"
+    )]
+    query: String,
+
+    #[schemars(description = "Character budget for the assembled context blob (default 8000)")]
+    max_chars: Option<u32>,
+
+    #[schemars(description = "Max files to search before ranking/packing (default 25)")]
+    limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct GetFileInput {
+    #[schemars(description = "Exact repository name, as shown by list_repos")]
+    repo: String,
+
+    #[schemars(description = "Exact file path within the repository, e.g. 'src/main.rs'")]
+    file_name: String,
+
+    #[schemars(description = "Branch to read from (default: no branch filter; the first match on any indexed branch is returned)")]
+    branch: Option<String>,
+}
+
+// ── ripgrep-style type: aliases ──────────────────────────────────────────────
+
+/// Built-in `type:` aliases, expanded to Zoekt `file:` regexes before a query is sent.
+/// Edit this table to add more languages.
+const TYPE_ALIASES: &[(&str, &str)] = &[
+    ("rust", r"\.rs$"),
+    ("py", r"\.(py|pyi)$"),
+    ("cpp", r"\.(cc|cpp|cxx|hpp|h)$"),
+    ("web", r"\.(ts|tsx|js|jsx)$"),
+    ("nix", r"\.nix$"),
+];
+
+/// Rewrite `type:<alias>` tokens in a query into the equivalent `file:<regex>` filter.
+/// Unknown `type:` tokens are passed through unchanged so Zoekt can report the error.
+fn expand_type_aliases(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|token| match token.strip_prefix("type:") {
+            Some(alias) => match TYPE_ALIASES.iter().find(|(name, _)| *name == alias) {
+                Some((_, pattern)) => format!("file:{pattern}"),
+                None => token.to_string(),
+            },
+            None => token.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// ── Tree-sitter scope expansion ──────────────────────────────────────────────
+
+/// Default cap on lines printed for one expanded scope, when the caller doesn't set
+/// `max_expand_lines`.
+const DEFAULT_MAX_EXPAND_LINES: u32 = 200;
+
+/// Look up a tree-sitter grammar and its "interesting" node kinds for a Zoekt `Language`
+/// string (as reported by go-enry, e.g. "Rust", "C++", "TypeScript").
+fn grammar_for_language(language: &str) -> Option<(tree_sitter::Language, &'static [&'static str])> {
+    match language {
+        "Rust" => Some((
+            tree_sitter_rust::language(),
+            &["function_item", "impl_item", "trait_item", "struct_item", "enum_item", "mod_item"],
+        )),
+        "Python" => Some((
+            tree_sitter_python::language(),
+            &["function_definition", "class_definition"],
+        )),
+        "C++" | "C" => Some((
+            tree_sitter_cpp::language(),
+            &["function_definition", "class_specifier", "struct_specifier", "namespace_definition"],
+        )),
+        "Java" => Some((
+            tree_sitter_java::language(),
+            &["method_declaration", "class_declaration", "interface_declaration"],
+        )),
+        "JavaScript" | "JSX" => Some((
+            tree_sitter_javascript::language(),
+            &["function_declaration", "method_definition", "class_declaration", "arrow_function"],
+        )),
+        "TypeScript" | "TSX" => Some((
+            tree_sitter_typescript::language_typescript(),
+            &["function_declaration", "method_definition", "class_declaration", "arrow_function"],
+        )),
+        "Go" => Some((
+            tree_sitter_go::language(),
+            &["function_declaration", "method_declaration", "type_declaration"],
+        )),
+        _ => None,
+    }
+}
+
+/// Walk up from the smallest node enclosing `byte_offset` to the nearest node whose kind is in
+/// `kinds`, returning its 1-based inclusive `(start_line, end_line)`.
+fn enclosing_scope(tree: &tree_sitter::Tree, byte_offset: usize, kinds: &[&str]) -> Option<(u32, u32)> {
+    let mut node = tree
+        .root_node()
+        .descendant_for_byte_range(byte_offset, byte_offset)?;
+    loop {
+        if kinds.contains(&node.kind()) {
+            return Some((node.start_position().row as u32 + 1, node.end_position().row as u32 + 1));
+        }
+        node = node.parent()?;
+    }
+}
+
+/// Clamp an expanded scope to `max_lines`, centering on the original match line if the scope
+/// is too large to print in full.
+fn clamp_scope(start: u32, end: u32, match_line: u32, max_lines: u32) -> (u32, u32) {
+    if end - start + 1 <= max_lines {
+        return (start, end);
+    }
+    let half = max_lines / 2;
+    let clamped_start = match_line.saturating_sub(half).max(start);
+    let clamped_end = (clamped_start + max_lines - 1).min(end);
+    (clamped_start, clamped_end)
+}
+
 // ── Zoekt Search API ────────────────────────────────────────────────────────
 
 #[derive(Serialize)]
@@ -258,12 +416,30 @@ fn decode_b64(s: &str) -> String {
         .unwrap_or_else(|| s.to_string())
 }
 
+/// Escape regex metacharacters so a literal repo/file name can be anchored in a Zoekt query.
+fn regex_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
 // ── MCP Server ──────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone)]
 struct ZoektMcp {
     client: reqwest::Client,
     base_url: String,
+    timeout: Duration,
+    // Keyed by caller-supplied search_id. The u64 is a per-registration sequence number so a
+    // call only ever tears down the entry it itself inserted, even if two concurrent searches
+    // reuse the same search_id.
+    inflight: Arc<Mutex<HashMap<String, (u64, CancellationToken)>>>,
+    next_search_seq: Arc<std::sync::atomic::AtomicU64>,
     tool_router: ToolRouter<Self>,
 }
 
@@ -272,9 +448,16 @@ impl ZoektMcp {
     fn new() -> Self {
         let base_url =
             std::env::var("ZOEKT_URL").unwrap_or_else(|_| "http://localhost:6070".to_string());
+        let timeout_ms: u64 = std::env::var("ZOEKT_TIMEOUT_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30_000);
         Self {
             client: reqwest::Client::new(),
             base_url,
+            timeout: Duration::from_millis(timeout_ms),
+            inflight: Arc::new(Mutex::new(HashMap::new())),
+            next_search_seq: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             tool_router: Self::tool_router(),
         }
     }
@@ -289,9 +472,16 @@ impl ZoektMcp {
             .client
             .post(&url)
             .json(body)
+            .timeout(self.timeout)
             .send()
             .await
-            .map_err(|e| format!("Cannot reach Zoekt at {url}: {e}"))?;
+            .map_err(|e| {
+                if e.is_timeout() {
+                    format!("search timed out after {} ms", self.timeout.as_millis())
+                } else {
+                    format!("Cannot reach Zoekt at {url}: {e}")
+                }
+            })?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -304,37 +494,323 @@ impl ZoektMcp {
             .map_err(|e| format!("Failed to parse Zoekt response: {e}"))
     }
 
+    /// Run `fut` to completion, registering `search_id` (if given) for the whole duration so
+    /// `cancel_search` can abort it mid-flight — whether `fut` is a single Zoekt request or a
+    /// multi-step pipeline (e.g. search + per-file expansion fetches).
+    async fn with_cancellation<T>(
+        &self,
+        search_id: Option<&str>,
+        fut: impl std::future::Future<Output = Result<T, String>>,
+    ) -> Result<T, String> {
+        let token = CancellationToken::new();
+        let seq = self
+            .next_search_seq
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if let Some(id) = search_id {
+            self.inflight
+                .lock()
+                .unwrap()
+                .insert(id.to_string(), (seq, token.clone()));
+        }
+
+        let result = tokio::select! {
+            res = fut => res,
+            _ = token.cancelled() => Err("search cancelled".to_string()),
+        };
+
+        if let Some(id) = search_id {
+            // Only tear down the entry if it's still the one we inserted — a concurrent search
+            // reusing the same search_id may have since overwritten it with its own registration.
+            let mut inflight = self.inflight.lock().unwrap();
+            if matches!(inflight.get(id), Some((s, _)) if *s == seq) {
+                inflight.remove(id);
+            }
+        }
+        result
+    }
+
     #[tool(
-        description = "Search code using Zoekt trigram index. Instant results over pre-indexed repositories. Supports regex and query filters: file:pattern lang:rust sym:funcname repo:name case:yes/no branch:name"
+        description = "Search code using Zoekt trigram index. Instant results over pre-indexed repositories. Supports regex and query filters: file:pattern lang:rust sym:funcname repo:name case:yes/no branch:name, plus ripgrep-style type:alias (e.g. type:rust — see the `types` tool). Pass search_id to allow aborting via cancel_search, expand_scope to show the enclosing function/class instead of a fixed context window, and offset (from a previous next_cursor) to page through large result sets."
     )]
     async fn search(&self, Parameters(input): Parameters<SearchInput>) -> String {
         let mode = input
             .output_mode
             .as_deref()
-            .unwrap_or("files_with_matches");
+            .unwrap_or("files_with_matches")
+            .to_string();
         let context = input.context_lines.unwrap_or(if mode == "content" { 2 } else { 0 });
         let limit = input.limit.unwrap_or(25);
+        let offset = input.offset.unwrap_or(0);
+        let expand_scope = input.expand_scope.unwrap_or(false);
+        let max_expand_lines = input.max_expand_lines.unwrap_or(DEFAULT_MAX_EXPAND_LINES);
+        let query = expand_type_aliases(&input.query);
+
+        // The whole pipeline — including any per-file expansion fetches below, often the
+        // slowest part — stays registered under search_id so cancel_search can abort it at
+        // any point, not just during the initial Zoekt request.
+        let pipeline = async {
+            // Zoekt has no native offset, so over-fetch to offset + limit and slice server-side.
+            let req = SearchRequest {
+                q: query,
+                opts: Some(SearchOpts {
+                    max_doc_display_count: offset.saturating_add(limit),
+                    num_context_lines: context,
+                    chunk_matches: mode == "content",
+                    whole: false,
+                }),
+            };
+            let parsed: SearchResponse = self.post("/api/search", &req).await?;
+            let mut result = parsed.result;
+            paginate_files(&mut result, offset, limit);
+
+            Ok(if mode == "content" && expand_scope {
+                self.format_content_expanded(&result, max_expand_lines, offset, limit)
+                    .await
+            } else {
+                match mode.as_str() {
+                    "content" => format_content(&result, offset, limit),
+                    "count" => format_count(&result, offset, limit),
+                    _ => format_files(&result, offset, limit),
+                }
+            })
+        };
+
+        self.with_cancellation(input.search_id.as_deref(), pipeline)
+            .await
+            .unwrap_or_else(|e| e)
+    }
+
+    /// Like `format_content`, but expands each match to its enclosing function/class via
+    /// tree-sitter instead of printing Zoekt's fixed context window.
+    async fn format_content_expanded(
+        &self,
+        result: &SearchResult,
+        max_lines: u32,
+        offset: u32,
+        limit: u32,
+    ) -> String {
+        let files = result.files.as_deref().unwrap_or_default();
+        let mut out = String::with_capacity(4096);
+        let _ = writeln!(
+            out,
+            "{} matches in {} files",
+            result.match_count, result.file_count
+        );
+
+        // Fetch every distinct (repository, file_name) needing expansion concurrently, rather
+        // than one HTTP round trip at a time — file_name alone would collide when a search
+        // spans repos that share a path, so key on the pair.
+        let mut seen = HashSet::new();
+        let fetch_keys: Vec<(String, String, Option<String>, String)> = files
+            .iter()
+            .filter(|file| file.chunk_matches.is_some())
+            .filter(|file| seen.insert((file.repository.clone(), file.file_name.clone())))
+            .map(|file| {
+                (
+                    file.repository.clone(),
+                    file.file_name.clone(),
+                    file.branches.first().cloned(),
+                    file.language.clone(),
+                )
+            })
+            .collect();
+
+        let cache: HashMap<(String, String), (String, Option<tree_sitter::Tree>)> = join_all(
+            fetch_keys
+                .into_iter()
+                .map(|(repo, file_name, branch, language)| async move {
+                    let whole = self
+                        .fetch_whole_file(&repo, &file_name, branch.as_deref())
+                        .await
+                        .ok()
+                        .and_then(|r| r.files.and_then(|f| f.into_iter().next()))
+                        .map(|f| decode_b64(&f.content))
+                        .unwrap_or_default();
+                    let tree = grammar_for_language(&language).and_then(|(lang, _)| {
+                        let mut parser = tree_sitter::Parser::new();
+                        parser.set_language(&lang).ok()?;
+                        parser.parse(&whole, None)
+                    });
+                    ((repo, file_name), (whole, tree))
+                }),
+        )
+        .await
+        .into_iter()
+        .collect();
+
+        for file in files {
+            let lang = if file.language.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", file.language)
+            };
+            let _ = writeln!(out, "\n--- {}{} ---", file.file_name, lang);
+
+            let Some(chunks) = &file.chunk_matches else {
+                continue;
+            };
+
+            let cache_key = (file.repository.clone(), file.file_name.clone());
+            let (whole_content, tree) = cache.get(&cache_key).unwrap();
+            let whole_lines: Vec<&str> = whole_content.lines().collect();
+            let kinds = grammar_for_language(&file.language).map(|(_, kinds)| kinds);
+
+            for chunk in chunks {
+                if let Some(syms) = &chunk.symbol_info {
+                    for sym in syms.iter().flatten() {
+                        let parent = if sym.parent.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" in {}", sym.parent)
+                        };
+                        let kind = if sym.kind.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" [{}]", sym.kind)
+                        };
+                        let _ = writeln!(out, "  symbol: {}{}{}", sym.sym, kind, parent);
+                    }
+                }
+
+                let content_start_line = chunk.content_start.line_number;
+                let match_line = chunk
+                    .ranges
+                    .first()
+                    .map(|r| r.start.line_number)
+                    .unwrap_or(content_start_line);
+                let byte_offset = chunk
+                    .ranges
+                    .first()
+                    .map(|r| r.start.byte_offset)
+                    .unwrap_or(chunk.content_start.byte_offset) as usize;
+
+                let expanded = tree.as_ref().zip(kinds).and_then(|(tree, kinds)| {
+                    enclosing_scope(tree, byte_offset, kinds)
+                        .map(|(start, end)| clamp_scope(start, end, match_line, max_lines))
+                });
+
+                match expanded {
+                    Some((start, end)) if !whole_lines.is_empty() => {
+                        for line_num in start..=end {
+                            let Some(text) = whole_lines.get(line_num as usize - 1) else {
+                                break;
+                            };
+                            let is_match = chunk
+                                .ranges
+                                .iter()
+                                .any(|r| line_num >= r.start.line_number && line_num <= r.end.line_number);
+                            let marker = if is_match { ">" } else { " " };
+                            let _ = writeln!(out, "{marker}{line_num}:{text}");
+                        }
+                    }
+                    _ => {
+                        // No grammar, no tree, or parse failed — fall back to Zoekt's context window.
+                        let content = decode_b64(&chunk.content);
+                        for (i, line) in content.lines().enumerate() {
+                            let line_num = content_start_line + i as u32;
+                            let is_match = chunk
+                                .ranges
+                                .iter()
+                                .any(|r| line_num >= r.start.line_number && line_num <= r.end.line_number);
+                            let marker = if is_match { ">" } else { " " };
+                            let _ = writeln!(out, "{marker}{line_num}:{line}");
+                        }
+                    }
+                }
+            }
+        }
+        write_next_cursor(&mut out, result.file_count, offset, limit);
+        out
+    }
+
+    #[tool(description = "List the built-in type: alias table (e.g. type:rust, type:cpp) that search expands into file: regexes")]
+    async fn types(&self) -> String {
+        format_type_aliases()
+    }
+
+    #[tool(
+        description = "Cancel an in-flight search() call by the search_id it was given. Has no effect if the search already finished."
+    )]
+    async fn cancel_search(&self, Parameters(input): Parameters<CancelSearchInput>) -> String {
+        // Cancel without removing the map entry — teardown is left to with_cancellation, which
+        // only removes the registration it itself inserted (see its seq check).
+        match self.inflight.lock().unwrap().get(&input.search_id) {
+            Some((_, token)) => {
+                token.cancel();
+                format!("Cancelled search {}", input.search_id)
+            }
+            None => format!("No in-flight search with id {}", input.search_id),
+        }
+    }
 
+    #[tool(
+        description = "Run a search and assemble the results into a single ranked, deduped, char-budgeted context blob ready to paste into a prompt, instead of a flat match list."
+    )]
+    async fn context_pack(&self, Parameters(input): Parameters<ContextPackInput>) -> String {
         let req = SearchRequest {
-            q: input.query,
+            q: expand_type_aliases(&input.query),
             opts: Some(SearchOpts {
-                max_doc_display_count: limit,
-                num_context_lines: context,
-                chunk_matches: mode == "content",
+                max_doc_display_count: input.limit.unwrap_or(25),
+                num_context_lines: 2,
+                chunk_matches: true,
                 whole: false,
             }),
         };
 
         match self.post::<_, SearchResponse>("/api/search", &req).await {
-            Ok(parsed) => match mode {
-                "content" => format_content(&parsed.result),
-                "count" => format_count(&parsed.result),
-                _ => format_files(&parsed.result),
-            },
+            Ok(parsed) => {
+                let max_chars = input.max_chars.unwrap_or(DEFAULT_MAX_CONTEXT_CHARS) as usize;
+                pack_context(&parsed.result, max_chars)
+            }
+            Err(e) => e,
+        }
+    }
+
+    #[tool(
+        description = "Fetch the complete contents of a single file, with line numbers. Use after search narrows down a file_name so you don't have to stitch chunk matches back together."
+    )]
+    async fn get_file(&self, Parameters(input): Parameters<GetFileInput>) -> String {
+        match self
+            .fetch_whole_file(&input.repo, &input.file_name, input.branch.as_deref())
+            .await
+        {
+            Ok(result) => format_whole_file(&result),
             Err(e) => e,
         }
     }
 
+    /// Fetch the whole contents of one file via a `Whole: true` search, scoped to an exact
+    /// repo + file_name (and optionally branch). Shared by `get_file` and `expand_scope`.
+    async fn fetch_whole_file(
+        &self,
+        repo: &str,
+        file_name: &str,
+        branch: Option<&str>,
+    ) -> Result<SearchResult, String> {
+        let mut q = format!(
+            "repo:^{}$ file:^{}$",
+            regex_escape(repo),
+            regex_escape(file_name)
+        );
+        if let Some(branch) = branch {
+            let _ = write!(q, " branch:{branch}");
+        }
+
+        let req = SearchRequest {
+            q,
+            opts: Some(SearchOpts {
+                max_doc_display_count: 1,
+                num_context_lines: 0,
+                chunk_matches: false,
+                whole: true,
+            }),
+        };
+
+        self.post::<_, SearchResponse>("/api/search", &req)
+            .await
+            .map(|parsed| parsed.result)
+    }
+
     #[tool(description = "List all repositories indexed by Zoekt with document counts and sizes")]
     async fn list_repos(&self, Parameters(input): Parameters<ListReposInput>) -> String {
         let req = ListRequest {
@@ -364,7 +840,7 @@ impl ServerHandler for ZoektMcp {
 
 // ── Formatting: content mode (matching lines with context) ──────────────────
 
-fn format_content(result: &SearchResult) -> String {
+fn format_content(result: &SearchResult, offset: u32, limit: u32) -> String {
     let files = result.files.as_deref().unwrap_or_default();
     let mut out = String::with_capacity(4096);
     let _ = writeln!(
@@ -442,12 +918,38 @@ fn format_content(result: &SearchResult) -> String {
             }
         }
     }
+    write_next_cursor(&mut out, result.file_count, offset, limit);
     out
 }
 
+// ── Cursor-based pagination ──────────────────────────────────────────────────
+
+/// Slice an already-fetched `offset + limit` window of files down to just `limit`, starting
+/// at `offset`. Zoekt has no native offset, so callers over-fetch and this does the paging.
+fn paginate_files(result: &mut SearchResult, offset: u32, limit: u32) {
+    if let Some(files) = result.files.take() {
+        result.files = Some(
+            files
+                .into_iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .collect(),
+        );
+    }
+}
+
+/// Append a `next_cursor` line if `file_count` extends past the `offset + limit` window just
+/// printed, so the caller can pass it back as `offset` to fetch the next page.
+fn write_next_cursor(out: &mut String, file_count: u64, offset: u32, limit: u32) {
+    let window_end = offset.saturating_add(limit) as u64;
+    if file_count > window_end {
+        let _ = writeln!(out, "next_cursor: {window_end}");
+    }
+}
+
 // ── Formatting: files_with_matches mode ─────────────────────────────────────
 
-fn format_files(result: &SearchResult) -> String {
+fn format_files(result: &SearchResult, offset: u32, limit: u32) -> String {
     let files = result.files.as_deref().unwrap_or_default();
     let mut out = String::with_capacity(1024);
     let _ = writeln!(
@@ -458,12 +960,13 @@ fn format_files(result: &SearchResult) -> String {
     for file in files {
         let _ = writeln!(out, "{}", file.file_name);
     }
+    write_next_cursor(&mut out, result.file_count, offset, limit);
     out
 }
 
 // ── Formatting: count mode ──────────────────────────────────────────────────
 
-fn format_count(result: &SearchResult) -> String {
+fn format_count(result: &SearchResult, offset: u32, limit: u32) -> String {
     let files = result.files.as_deref().unwrap_or_default();
     let mut out = String::with_capacity(1024);
     let _ = writeln!(
@@ -480,6 +983,120 @@ fn format_count(result: &SearchResult) -> String {
             .unwrap_or(0);
         let _ = writeln!(out, "{}:{}", file.file_name, count);
     }
+    write_next_cursor(&mut out, result.file_count, offset, limit);
+    out
+}
+
+// ── Formatting: get_file (whole-file mode) ──────────────────────────────────
+
+fn format_whole_file(result: &SearchResult) -> String {
+    let files = result.files.as_deref().unwrap_or_default();
+    let Some(file) = files.first() else {
+        return "No matching file".to_string();
+    };
+
+    let lang = if file.language.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", file.language)
+    };
+    let mut out = String::with_capacity(4096);
+    let _ = writeln!(out, "--- {}{} ---", file.file_name, lang);
+
+    let content = decode_b64(&file.content);
+    for (i, line) in content.lines().enumerate() {
+        let _ = writeln!(out, "{}:{}", i + 1, line);
+    }
+    out
+}
+
+// ── Formatting: type: aliases ────────────────────────────────────────────────
+
+fn format_type_aliases() -> String {
+    let mut out = String::with_capacity(256);
+    let _ = writeln!(out, "{} type: aliases", TYPE_ALIASES.len());
+    for (name, pattern) in TYPE_ALIASES {
+        let _ = writeln!(out, "  type:{name} -> file:{pattern}");
+    }
+    out
+}
+
+// ── Formatting: context_pack ─────────────────────────────────────────────────
+
+/// Default character budget for `context_pack`, when the caller doesn't set `max_chars`.
+const DEFAULT_MAX_CONTEXT_CHARS: u32 = 8000;
+
+/// Rank chunk matches by score, drop ones whose line range overlaps an already-kept chunk in
+/// the same file, and greedily pack the rest into `max_chars`, each with a `repo:path:line`
+/// citation header. Packing stops at the first chunk that does not fit; the top-ranked chunk
+/// is always kept, even when it alone exceeds `max_chars`.
+fn pack_context(result: &SearchResult, max_chars: usize) -> String {
+    let files = result.files.as_deref().unwrap_or_default();
+
+    let mut items: Vec<(&FileMatch, &ChunkMatch)> = Vec::new();
+    for file in files {
+        if let Some(chunks) = &file.chunk_matches {
+            for chunk in chunks {
+                items.push((file, chunk));
+            }
+        }
+    }
+    items.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+
+    // Keyed by (repository, file_name) — file_name alone collides when results span repos
+    // that share a path.
+    let mut kept_ranges: HashMap<(&str, &str), Vec<(u32, u32)>> = HashMap::new();
+    let mut out = String::with_capacity(max_chars.min(65536));
+    let mut budget = max_chars;
+    let mut kept = 0usize;
+
+    for (file, chunk) in items {
+        let content = decode_b64(&chunk.content);
+        let start = chunk.content_start.line_number;
+        let end = start + content.lines().count().saturating_sub(1) as u32;
+
+        let ranges = kept_ranges
+            .entry((file.repository.as_str(), file.file_name.as_str()))
+            .or_default();
+        if ranges.iter().any(|&(s, e)| start <= e && end >= s) {
+            continue;
+        }
+
+        let mut block = String::new();
+        let _ = writeln!(block, "{}:{}:{}", file.repository, file.file_name, start);
+        if let Some(syms) = &chunk.symbol_info {
+            for sym in syms.iter().flatten() {
+                let kind = if sym.kind.is_empty() {
+                    String::new()
+                } else {
+                    format!(" [{}]", sym.kind)
+                };
+                let parent = if sym.parent.is_empty() {
+                    String::new()
+                } else {
+                    format!(" in {}", sym.parent)
+                };
+                let _ = writeln!(block, "  {}{}{}", sym.sym, kind, parent);
+            }
+        }
+        let _ = writeln!(block, "{}", content.trim_end());
+        block.push('\n');
+
+        if kept > 0 && block.len() > budget {
+            break;
+        }
+        ranges.push((start, end));
+        budget = budget.saturating_sub(block.len());
+        out.push_str(&block);
+        kept += 1;
+        if budget == 0 {
+            break;
+        }
+    }
+
+    if kept == 0 {
+        return "No matches".to_string();
+    }
     out
 }
 